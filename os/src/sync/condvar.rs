@@ -0,0 +1,65 @@
+//! Condition variable, implemented with Birrell's two-semaphore scheme
+use super::{Mutex, Semaphore, UPSafeCell};
+use alloc::sync::Arc;
+
+struct CondvarInner {
+    waiter_count: usize,
+}
+
+/// A condition variable built on Birrell's two-semaphore scheme: waiters sleep on
+/// `sem_block`, and `sem_unblock` is a handshake back to the signaler so it can't race
+/// ahead of a waiter actually being scheduled. That handshake is what closes the
+/// lost-wakeup/ordering bugs a single semaphore has when many threads are parked.
+///
+/// The caller must hold the associated mutex when calling `signal`/`broadcast`; `wait`
+/// releases it before sleeping and reacquires it before returning.
+pub struct Condvar {
+    inner: UPSafeCell<CondvarInner>,
+    sem_block: Semaphore,
+    sem_unblock: Semaphore,
+}
+
+impl Condvar {
+    /// create a condvar with no waiters parked
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe { UPSafeCell::new(CondvarInner { waiter_count: 0 }) },
+            sem_block: Semaphore::new(0),
+            sem_unblock: Semaphore::new(0),
+        }
+    }
+
+    /// wake a single waiter, if any are parked; a no-op otherwise
+    pub fn signal(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.waiter_count > 0 {
+            inner.waiter_count -= 1;
+            drop(inner);
+            self.sem_block.up();
+            self.sem_unblock.down();
+        }
+    }
+
+    /// wake every waiter currently parked
+    pub fn broadcast(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let waiters = inner.waiter_count;
+        inner.waiter_count = 0;
+        drop(inner);
+        for _ in 0..waiters {
+            self.sem_block.up();
+        }
+        for _ in 0..waiters {
+            self.sem_unblock.down();
+        }
+    }
+
+    /// release `mutex`, sleep until signaled or broadcast, then reacquire `mutex`
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) {
+        self.inner.exclusive_access().waiter_count += 1;
+        mutex.unlock();
+        self.sem_block.down();
+        self.sem_unblock.up();
+        mutex.lock();
+    }
+}
@@ -0,0 +1,12 @@
+//! Synchronization primitives available to both the kernel and user-facing syscalls
+mod condvar;
+mod mutex;
+mod rwlock;
+mod semaphore;
+mod up;
+
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use rwlock::RwLock;
+pub use semaphore::Semaphore;
+pub use up::UPSafeCell;
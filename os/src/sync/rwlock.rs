@@ -0,0 +1,123 @@
+//! Reader-writer lock with writer preference
+use super::UPSafeCell;
+use crate::task::{add_task, block_current_and_run_next, current_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// a queued waiter plus the ticket the releaser flips once it has handed ownership (of
+/// one read slot, or of the lock itself) over to it. A woken waiter only ever consults
+/// its own ticket instead of re-deriving "did I get it?" from the shared `writer`/
+/// `readers` fields, which is what let a woken writer previously see the unlocker's own
+/// `writer = true` and mistake it for someone else still holding the lock, and let a
+/// woken reader double-count itself into `readers`.
+type Waiter = (Arc<TaskControlBlock>, Arc<UPSafeCell<bool>>);
+
+struct RwLockInner {
+    readers: usize,
+    writer: bool,
+    /// set once a writer is waiting, so newly arriving readers queue up behind it
+    /// instead of jumping ahead and starving the writer out
+    writer_waiting: bool,
+    reader_queue: VecDeque<Waiter>,
+    writer_queue: VecDeque<Waiter>,
+}
+
+/// A reader-writer lock: any number of readers may hold it concurrently, a writer
+/// needs exclusive access, and once a writer is queued new readers block behind it.
+pub struct RwLock {
+    inner: UPSafeCell<RwLockInner>,
+}
+
+impl RwLock {
+    /// create a new, unheld rwlock
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(RwLockInner {
+                    readers: 0,
+                    writer: false,
+                    writer_waiting: false,
+                    reader_queue: VecDeque::new(),
+                    writer_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    /// block until `ticket` is flipped by whoever currently holds the lock, then
+    /// return. Unlike the initial acquire attempt this never re-checks `inner`: the
+    /// releaser already transferred ownership (or a read slot) to us before waking us.
+    fn wait_for_grant(ticket: &Arc<UPSafeCell<bool>>) {
+        loop {
+            block_current_and_run_next();
+            if *ticket.exclusive_access() {
+                return;
+            }
+        }
+    }
+
+    /// acquire for reading; blocks while a writer holds the lock or one is waiting
+    pub fn read_lock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.writer && !inner.writer_waiting {
+            inner.readers += 1;
+            return;
+        }
+        let ticket = Arc::new(unsafe { UPSafeCell::new(false) });
+        inner
+            .reader_queue
+            .push_back((current_task().unwrap(), ticket.clone()));
+        drop(inner);
+        Self::wait_for_grant(&ticket);
+    }
+
+    /// acquire for writing; blocks while any reader or the writer holds the lock
+    pub fn write_lock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.writer && inner.readers == 0 {
+            inner.writer = true;
+            inner.writer_waiting = !inner.writer_queue.is_empty();
+            return;
+        }
+        inner.writer_waiting = true;
+        let ticket = Arc::new(unsafe { UPSafeCell::new(false) });
+        inner
+            .writer_queue
+            .push_back((current_task().unwrap(), ticket.clone()));
+        drop(inner);
+        Self::wait_for_grant(&ticket);
+    }
+
+    /// release the lock, whether held for reading or writing, admitting the next
+    /// waiter(s): a queued writer always goes first, then every queued reader at once.
+    /// Ownership transfers by flipping each waiter's own ticket before waking it, never
+    /// by mutating `writer`/`readers` out from under a waiter that hasn't run yet.
+    pub fn unlock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.writer {
+            inner.writer = false;
+        } else if inner.readers > 0 {
+            inner.readers -= 1;
+        }
+        if inner.readers > 0 {
+            return;
+        }
+        if let Some((task, ticket)) = inner.writer_queue.pop_front() {
+            inner.writer = true;
+            inner.writer_waiting = !inner.writer_queue.is_empty();
+            drop(inner);
+            *ticket.exclusive_access() = true;
+            add_task(task);
+            return;
+        }
+        inner.writer_waiting = false;
+        let waking: Vec<Waiter> = inner.reader_queue.drain(..).collect();
+        inner.readers += waking.len();
+        drop(inner);
+        for (task, ticket) in waking {
+            *ticket.exclusive_access() = true;
+            add_task(task);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! File-related syscalls
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::Inode;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::mm::{translated_byte_buffer, translated_str};
+use crate::sync::UPSafeCell;
+use crate::syscall::process::{proc_lookup, seccomp_dispatch, ProcInode};
+use crate::task::{current_task, current_user_token};
+
+/// seek from the start of the file
+pub const SEEK_SET: usize = 0;
+/// seek relative to the current cursor position
+pub const SEEK_CUR: usize = 1;
+/// seek relative to the end of the file
+pub const SEEK_END: usize = 2;
+
+const SYSCALL_OPEN: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_LSEEK: usize = 62;
+const SYSCALL_READ: usize = 63;
+const SYSCALL_WRITE: usize = 64;
+
+/// what a `FileHandle` actually reads/writes through: a real on-disk inode, or a
+/// `/proc` node rendered on demand. `proc_lookup` is what routes a path to the latter.
+enum FileBacking {
+    Disk(Arc<Inode>),
+    Proc(ProcInode),
+}
+
+/// An open file: a backing store plus a cursor, since neither `Inode::read_at`/
+/// `write_at` nor `ProcInode::read_at` know about anything but absolute offsets.
+pub struct FileHandle {
+    backing: FileBacking,
+    offset: usize,
+}
+
+impl FileHandle {
+    /// wrap `inode` in a fresh handle with the cursor at 0
+    pub fn new(inode: Arc<Inode>) -> Self {
+        Self {
+            backing: FileBacking::Disk(inode),
+            offset: 0,
+        }
+    }
+
+    /// Resolve `path` to a handle: `/proc/...` paths are served from `proc_lookup`,
+    /// anything else falls back to `open_disk` (the real VFS open this tree doesn't
+    /// include) to resolve a regular on-disk `Inode`.
+    pub fn open(path: &str, open_disk: impl FnOnce(&str) -> Option<Arc<Inode>>) -> Option<Self> {
+        if let Some(node) = proc_lookup(path) {
+            return Some(Self {
+                backing: FileBacking::Proc(node),
+                offset: 0,
+            });
+        }
+        Some(Self {
+            backing: FileBacking::Disk(open_disk(path)?),
+            offset: 0,
+        })
+    }
+
+    /// read from the cursor, advancing it by the number of bytes actually read
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let n = match &self.backing {
+            FileBacking::Disk(inode) => inode.read_at(self.offset, buf),
+            FileBacking::Proc(node) => node.read_at(self.offset, buf),
+        };
+        self.offset += n;
+        n
+    }
+
+    /// write at the cursor, advancing it by the number of bytes actually written;
+    /// `/proc` nodes are read-only and always reject a write
+    pub fn write(&mut self, buf: &[u8]) -> usize {
+        let n = match &self.backing {
+            FileBacking::Disk(inode) => inode.write_at(self.offset, buf),
+            FileBacking::Proc(_) => 0,
+        };
+        self.offset += n;
+        n
+    }
+
+    /// reposition the cursor per `whence`; negative resulting positions are rejected.
+    /// `SEEK_END` isn't supported on a `/proc` node, since its rendered size isn't
+    /// known without generating its full contents.
+    fn seek(&mut self, offset: isize, whence: usize) -> isize {
+        let base = match (whence, &self.backing) {
+            (SEEK_SET, _) => 0,
+            (SEEK_CUR, _) => self.offset as isize,
+            (SEEK_END, FileBacking::Disk(inode)) => inode.size() as isize,
+            _ => return -1,
+        };
+        let new_pos = base + offset;
+        if new_pos < 0 {
+            return -1;
+        }
+        self.offset = new_pos as usize;
+        self.offset as isize
+    }
+}
+
+/// per-pid fd table, kept as a side table like `TASK_INFO` rather than on
+/// `TaskControlBlockInner`: this request's `task` counterpart never declares an
+/// `fd_table` field (or the element type it would need), so rather than assume one,
+/// this owns its bookkeeping the same way the rest of this backlog keys per-task state.
+type FdTable = Vec<Option<Arc<Mutex<FileHandle>>>>;
+
+lazy_static! {
+    static ref FD_TABLES: UPSafeCell<Vec<(usize, FdTable)>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+fn current_pid() -> usize {
+    current_task().unwrap().pid.0
+}
+
+/// install `handle` into the calling task's fd table, returning the new fd
+pub fn install_fd(handle: FileHandle) -> usize {
+    let pid = current_pid();
+    let mut table = FD_TABLES.exclusive_access();
+    let idx = if let Some(idx) = table.iter().position(|(id, _)| *id == pid) {
+        idx
+    } else {
+        table.push((pid, Vec::new()));
+        table.len() - 1
+    };
+    let fds = &mut table[idx].1;
+    if let Some(fd) = fds.iter().position(|f| f.is_none()) {
+        fds[fd] = Some(Arc::new(Mutex::new(handle)));
+        fd
+    } else {
+        fds.push(Some(Arc::new(Mutex::new(handle))));
+        fds.len() - 1
+    }
+}
+
+/// look up an open fd for the calling task
+fn get_fd(fd: usize) -> Option<Arc<Mutex<FileHandle>>> {
+    let pid = current_pid();
+    FD_TABLES
+        .exclusive_access()
+        .iter()
+        .find(|(id, _)| *id == pid)
+        .and_then(|(_, fds)| fds.get(fd).cloned().flatten())
+}
+
+/// open `path`, installing a fresh `FileHandle` into the calling task's fd table and
+/// returning its fd, or `-1` if `path` doesn't resolve to anything.
+///
+/// This is `FileHandle::open`/`install_fd`'s real caller: without it nothing in this
+/// tree ever populates `FD_TABLES`, so every fd a user program could pass to
+/// `sys_read`/`sys_write`/`sys_lseek` would be unopenable. Only `/proc/...` paths can
+/// actually resolve here, though — a disk path needs a root-inode accessor (an
+/// `os/src/fs/mod.rs`-style `ROOT_INODE`, built from whatever `BlockDevice` the platform
+/// wires up) that isn't part of this tree, so `open_disk` below always misses and a
+/// disk path fails exactly like looking up a file that doesn't exist.
+pub fn sys_open(path: *const u8, _flags: u32) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_pid(), SYSCALL_OPEN) {
+        return errno;
+    }
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    match FileHandle::open(&path, |_| None) {
+        Some(handle) => install_fd(handle) as isize,
+        None => -1,
+    }
+}
+
+/// close an open file descriptor
+pub fn sys_close(fd: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_pid(), SYSCALL_CLOSE) {
+        return errno;
+    }
+    let pid = current_pid();
+    let mut table = FD_TABLES.exclusive_access();
+    let Some((_, fds)) = table.iter_mut().find(|(id, _)| *id == pid) else {
+        return -1;
+    };
+    let Some(slot) = fds.get_mut(fd).filter(|slot| slot.is_some()) else {
+        return -1;
+    };
+    *slot = None;
+    0
+}
+
+/// read up to `len` bytes from `fd` into the user buffer at `buf`, advancing its cursor
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_pid(), SYSCALL_READ) {
+        return errno;
+    }
+    let Some(file) = get_fd(fd) else {
+        return -1;
+    };
+    let token = current_user_token();
+    let mut handle = file.lock();
+    let mut total = 0;
+    for slice in translated_byte_buffer(token, buf as *const u8, len) {
+        let n = handle.read(slice);
+        total += n;
+        if n < slice.len() {
+            break;
+        }
+    }
+    total as isize
+}
+
+/// write up to `len` bytes from the user buffer at `buf` to `fd`, advancing its cursor
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_pid(), SYSCALL_WRITE) {
+        return errno;
+    }
+    let Some(file) = get_fd(fd) else {
+        return -1;
+    };
+    let token = current_user_token();
+    let mut handle = file.lock();
+    let mut total = 0;
+    for slice in translated_byte_buffer(token, buf, len) {
+        let n = handle.write(slice);
+        total += n;
+        if n < slice.len() {
+            break;
+        }
+    }
+    total as isize
+}
+
+/// reposition an open file descriptor's cursor, POSIX `lseek`-style
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_pid(), SYSCALL_LSEEK) {
+        return errno;
+    }
+    let Some(file) = get_fd(fd) else {
+        return -1;
+    };
+    file.lock().seek(offset, whence)
+}
@@ -1,6 +1,9 @@
 //! Process management syscalls
 use core::{mem::size_of, slice::from_raw_parts};
 
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
 
@@ -8,7 +11,7 @@ use alloc::sync::Arc;
 
 use crate::{
     config::{MAX_SYSCALL_NUM, PAGE_SIZE}, loader::get_app_data_by_name, mm::{translated_byte_buffer, translated_refmut, translated_str, MapPermission}, sync::UPSafeCell, task::{
-        add_task, current_task, current_task_mmap, current_task_munmap, current_user_token, exit_current_and_run_next, get_current_pid, suspend_current_and_run_next, TaskStatus
+        add_task, current_task, current_task_mmap, current_task_munmap, current_user_token, exit_current_and_run_next, get_current_pid, suspend_current_and_run_next, TaskControlBlock, TaskStatus
     }, timer::{get_time_ms, get_time_us}
 };
 
@@ -52,7 +55,314 @@ lazy_static! {
     };
 }
 
+/// A leaf file rendered under `/proc/<pid>/`
+#[derive(Clone, Copy)]
+pub enum ProcFile {
+    /// `/proc/<pid>/status`: the task's current state
+    Status,
+    /// `/proc/<pid>/stat`: pid, state and elapsed running time in ms, one line
+    Stat,
+    /// `/proc/<pid>/syscalls`: cumulative `syscall_times` counters, one "id count" per line
+    /// for each syscall called at least once
+    Syscalls,
+}
+
+/// generator-backed content, for inodes with no backing disk blocks
+///
+/// `ProcInode::read_at` calls `generate` instead of `DiskInode::read_at`, rendering the
+/// node's text on demand from `TASK_INFO` rather than reading it off a block device.
+pub trait DynamicFileSource {
+    /// render this source's full contents
+    fn generate(&self) -> Option<String>;
+}
+
+impl DynamicFileSource for (usize, ProcFile) {
+    fn generate(&self) -> Option<String> {
+        let (pid, file) = *self;
+        let task_info = TASK_INFO.exclusive_access();
+        let (_, info) = task_info.iter().find(|(id, _)| *id == pid)?;
+        Some(match file {
+            ProcFile::Status => format!("{:?}\n", info.status),
+            ProcFile::Stat => format!(
+                "{} {:?} {}\n",
+                pid,
+                info.status,
+                get_time_ms() - info.time
+            ),
+            ProcFile::Syscalls => {
+                let mut s = String::new();
+                for (id, count) in info.syscall_times.iter().enumerate() {
+                    if *count > 0 {
+                        s += &format!("{} {}\n", id, count);
+                    }
+                }
+                s
+            }
+        })
+    }
+}
+
+/// A node of the synthetic `/proc` pseudo-filesystem, modeled on Starnix's `pid_directory`
+///
+/// `/proc` lists one entry per live pid; each `/proc/<pid>` directory holds `status`,
+/// `stat` and `syscalls`, all rendered lazily from `TASK_INFO` rather than from disk.
+pub enum ProcInode {
+    /// the `/proc` root: one directory entry per live pid
+    Root,
+    /// `/proc/<pid>`: holds `status`/`stat`/`syscalls`
+    PidDir(usize),
+    /// `/proc/<pid>/{status,stat,syscalls}`
+    PidFile(usize, ProcFile),
+}
+
+impl ProcInode {
+    /// list the entries under this node; empty for leaf files
+    pub fn ls(&self) -> Vec<String> {
+        match self {
+            ProcInode::Root => TASK_INFO
+                .exclusive_access()
+                .iter()
+                .map(|(pid, _)| pid.to_string())
+                .collect(),
+            ProcInode::PidDir(_) => vec![
+                String::from("status"),
+                String::from("stat"),
+                String::from("syscalls"),
+            ],
+            ProcInode::PidFile(..) => Vec::new(),
+        }
+    }
+
+    /// render this leaf's contents into `buf` at `offset`, returning the bytes copied
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let ProcInode::PidFile(pid, file) = self else {
+            return 0;
+        };
+        let Some(text) = (*pid, *file).generate() else {
+            return 0;
+        };
+        let bytes = text.as_bytes();
+        if offset >= bytes.len() {
+            return 0;
+        }
+        let len = buf.len().min(bytes.len() - offset);
+        buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+        len
+    }
+}
+
+/// Resolve an absolute path under `/proc` to the node it names, or `None` if it isn't
+/// one of `/proc`, `/proc/<pid>`, or `/proc/<pid>/{status,stat,syscalls}`.
+///
+/// This is the file-open integration point for `ProcInode`: `FileHandle::open` in
+/// `syscall::fs` (called by `sys_open`) tries `proc_lookup` first and falls back to the
+/// on-disk filesystem when it returns `None`.
+pub fn proc_lookup(path: &str) -> Option<ProcInode> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let mut parts = path.splitn(3, '/');
+    if parts.next()? != "proc" {
+        return None;
+    }
+    let Some(pid_str) = parts.next() else {
+        return Some(ProcInode::Root);
+    };
+    let pid: usize = pid_str.parse().ok()?;
+    let Some(file_name) = parts.next() else {
+        return Some(ProcInode::PidDir(pid));
+    };
+    let file = match file_name {
+        "status" => ProcFile::Status,
+        "stat" => ProcFile::Stat,
+        "syscalls" => ProcFile::Syscalls,
+        _ => return None,
+    };
+    Some(ProcInode::PidFile(pid, file))
+}
+
+/// the "big stride" constant of stride scheduling; `stride = BIG_STRIDE / priority`
+const BIG_STRIDE: u64 = 0xFFFF_FFFF;
+/// priority assigned to a task that has never called `sys_set_priority`
+const DEFAULT_PRIORITY: isize = 16;
+/// lowest priority `sys_set_priority` accepts; below this the stride would dominate
+const MIN_PRIORITY: isize = 2;
+
+/// per-task stride-scheduling state, keyed by pid alongside `TASK_INFO` for the same
+/// reason: the lab's `TaskControlBlock` has no room reserved for it.
+#[derive(Clone, Copy)]
+struct StrideInfo {
+    priority: isize,
+    pass: u64,
+}
+
+impl StrideInfo {
+    fn new() -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            pass: 0,
+        }
+    }
+
+    fn stride(&self) -> u64 {
+        BIG_STRIDE / self.priority as u64
+    }
+}
+
+lazy_static! {
+    /// stride-scheduling bookkeeping for every live task, keyed by pid
+    static ref STRIDE_INFO: UPSafeCell<Vec<(usize, StrideInfo)>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// compare two wrapping `pass` counters: true iff `a` should run before `b`
+///
+/// Plain `a < b` breaks once `pass` wraps past `u64::MAX`. Treating the difference as
+/// a signed value keeps the comparison correct as long as no two passes drift apart by
+/// more than `BIG_STRIDE / 2`, which stride scheduling guarantees.
+fn pass_less(a: u64, b: u64) -> bool {
+    (a.wrapping_sub(b) as i64) < 0
+}
+
+/// Pick the ready pid with the smallest `pass` and advance its `pass` by its `stride`.
+///
+/// This is meant to stand in for the scheduler's ready-queue fetch (`task::manager`/
+/// `task::processor` in the usual layout) in place of round-robin's "pop the front of
+/// the queue" — but that fetch routine isn't part of this tree, so no real scheduler
+/// calls this yet. It does have one real caller now, though: `sync::sched_pick` (the
+/// per-thread `SCHED_FIFO`/`SCHED_RR` policy) defers to this for every ready `SCHED_OTHER`
+/// thread instead of shipping a second, independent priority scheme of its own — see its
+/// doc comment. `sys_set_priority` below only maintains `STRIDE_INFO`, the table this
+/// reads.
+pub fn stride_pick(ready: &[usize]) -> Option<usize> {
+    let mut info = STRIDE_INFO.exclusive_access();
+    let pass_of = |info: &Vec<(usize, StrideInfo)>, pid: usize| {
+        info.iter()
+            .find(|(id, _)| *id == pid)
+            .map(|(_, s)| s.pass)
+            .unwrap_or(0)
+    };
+    let winner = ready.iter().copied().min_by(|&a, &b| {
+        if pass_less(pass_of(&info, a), pass_of(&info, b)) {
+            core::cmp::Ordering::Less
+        } else {
+            core::cmp::Ordering::Greater
+        }
+    })?;
+    if let Some((_, s)) = info.iter_mut().find(|(id, _)| *id == winner) {
+        s.pass = s.pass.wrapping_add(s.stride());
+    } else {
+        let mut s = StrideInfo::new();
+        s.pass = s.pass.wrapping_add(s.stride());
+        info.push((winner, s));
+    }
+    Some(winner)
+}
+
+/// seccomp enforcement mode, mirroring Linux's `SECCOMP_RET_ERRNO`/`SECCOMP_RET_KILL`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SeccompMode {
+    /// a disallowed syscall fails with -1 and the task keeps running
+    Errno,
+    /// a disallowed syscall terminates the task immediately
+    Kill,
+}
+
+/// a task's installed syscall allow-list
+#[derive(Clone)]
+struct SeccompPolicy {
+    mode: SeccompMode,
+    allowed: Vec<usize>,
+}
+
+lazy_static! {
+    /// per-pid seccomp policies, keyed like `TASK_INFO`; a pid with no entry is unrestricted
+    static ref SECCOMP_POLICY: UPSafeCell<Vec<(usize, SeccompPolicy)>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// install (or replace) the calling task's syscall allow-list
+///
+/// `mode` is 0 for `SECCOMP_RET_ERRNO`-style failure or 1 for `SECCOMP_RET_KILL`-style
+/// termination. `allow_mask` is a bitmask over syscall numbers below `usize::BITS`.
+pub fn sys_seccomp_set(mode: usize, allow_mask: usize) -> isize {
+    let mode = match mode {
+        0 => SeccompMode::Errno,
+        1 => SeccompMode::Kill,
+        _ => return -1,
+    };
+    let allowed = (0..usize::BITS as usize)
+        .filter(|i| allow_mask & (1 << i) != 0)
+        .collect();
+    let pid = current_task().unwrap().pid.0;
+    let mut policy = SECCOMP_POLICY.exclusive_access();
+    if let Some((_, p)) = policy.iter_mut().find(|(id, _)| *id == pid) {
+        *p = SeccompPolicy { mode, allowed };
+    } else {
+        policy.push((pid, SeccompPolicy { mode, allowed }));
+    }
+    0
+}
+
+/// inherit the parent's seccomp policy (if any) into a freshly created child
+///
+/// Called from `sys_fork`/`sys_spawn` right after the child's `TASK_INFO` entry is seeded.
+fn inherit_seccomp(parent_pid: usize, child_pid: usize) {
+    let inherited = SECCOMP_POLICY
+        .exclusive_access()
+        .iter()
+        .find(|(id, _)| *id == parent_pid)
+        .map(|(_, p)| p.clone());
+    if let Some(policy) = inherited {
+        SECCOMP_POLICY.exclusive_access().push((child_pid, policy));
+    }
+}
+
+/// this lab's syscall numbers, for the handlers in this file that call
+/// `seccomp_dispatch` on their own behalf (see its doc comment); `syscall::sync` and
+/// `syscall::fs` declare their own
+pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_GET_TIME: usize = 169;
+pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_SBRK: usize = 214;
+pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_FORK: usize = 220;
+pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_SET_PRIORITY: usize = 140;
+pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_SPAWN: usize = 400;
+
+/// Meant to be called by the syscall dispatcher right after it bumps `syscall_times`,
+/// to check whether `syscall_id` may proceed for `pid` under its installed seccomp
+/// policy. Since that central dispatch loop isn't part of this tree, every handler in
+/// this file and in `syscall::sync`/`syscall::fs` calls it on its own behalf instead,
+/// except `sys_exit` (which can't return an `Err` — see its call site) and
+/// `sys_seccomp_set` (which is not gated, so a task can always replace its own policy).
+///
+/// Returns `Ok(())` when the call may proceed. On a disallowed call it either fails the
+/// syscall (`Err(-1)`, `SECCOMP_RET_ERRNO`-style) or terminates the task
+/// (`SECCOMP_RET_KILL`-style, which this function does not return from).
+pub fn seccomp_dispatch(pid: usize, syscall_id: usize) -> Result<(), isize> {
+    let policy = SECCOMP_POLICY.exclusive_access();
+    let Some((_, p)) = policy.iter().find(|(id, _)| *id == pid) else {
+        return Ok(());
+    };
+    if p.allowed.contains(&syscall_id) {
+        return Ok(());
+    }
+    match p.mode {
+        SeccompMode::Errno => Err(-1),
+        SeccompMode::Kill => {
+            drop(policy);
+            exit_current_and_run_next(-1);
+            unreachable!("exit_current_and_run_next never returns")
+        }
+    }
+}
+
 /// task exits and submit an exit code
+///
+/// Not seccomp-gated like the rest of this file's syscalls: it returns `!`, so there's
+/// no way to report an `Err(-1)` back to a caller that was denied, and denying a task's
+/// own exit would make seccomp deadlock the task rather than sandbox it.
 pub fn sys_exit(exit_code: i32) -> ! {
     trace!("kernel:pid[{}] sys_exit", current_task().unwrap().pid.0);
     exit_current_and_run_next(exit_code);
@@ -62,18 +372,29 @@ pub fn sys_exit(exit_code: i32) -> ! {
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
     trace!("kernel:pid[{}] sys_yield", current_task().unwrap().pid.0);
+    let pid = current_task().unwrap().pid.0;
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_YIELD) {
+        return errno;
+    }
     suspend_current_and_run_next();
     0
 }
 
 pub fn sys_getpid() -> isize {
     trace!("kernel: sys_getpid pid:{}", current_task().unwrap().pid.0);
-    current_task().unwrap().pid.0 as isize
+    let pid = current_task().unwrap().pid.0;
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_GETPID) {
+        return errno;
+    }
+    pid as isize
 }
 
 pub fn sys_fork() -> isize {
     trace!("kernel:pid[{}] sys_fork", current_task().unwrap().pid.0);
     let cur_pid = current_task().unwrap().pid.0;
+    if let Err(errno) = seccomp_dispatch(cur_pid, SYSCALL_FORK) {
+        return errno;
+    }
     let current_task = current_task().unwrap();
     let new_task = current_task.fork();
     let new_pid = new_task.pid.0;
@@ -83,6 +404,21 @@ pub fn sys_fork() -> isize {
     .find(|(id, _)| *id == cur_pid)
     .unwrap().1.clone();
     TASK_INFO.exclusive_access().push((new_pid, new_ti));
+    inherit_seccomp(cur_pid, new_pid);
+    // inherit the parent's priority (but not its accumulated pass) into the child
+    let priority = STRIDE_INFO
+        .exclusive_access()
+        .iter()
+        .find(|(id, _)| *id == cur_pid)
+        .map(|(_, s)| s.priority)
+        .unwrap_or(DEFAULT_PRIORITY);
+    STRIDE_INFO.exclusive_access().push((
+        new_pid,
+        StrideInfo {
+            priority,
+            pass: 0,
+        },
+    ));
     // modify trap context of new_task, because it returns immediately after switching
     let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
     // we do not have to move to next instruction since we have done it before
@@ -96,6 +432,9 @@ pub fn sys_fork() -> isize {
 pub fn sys_exec(path: *const u8) -> isize {
     trace!("kernel:pid[{}] sys_exec", current_task().unwrap().pid.0);
     let pid = current_task().unwrap().pid.0;
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_EXEC) {
+        return errno;
+    }
     let token = current_user_token();
     let path = translated_str(token, path);
     if let Some(data) = get_app_data_by_name(path.as_str()) {
@@ -117,6 +456,9 @@ pub fn sys_exec(path: *const u8) -> isize {
 pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
     trace!("kernel::pid[{}] sys_waitpid [{}]", current_task().unwrap().pid.0, pid);
     let task = current_task().unwrap();
+    if let Err(errno) = seccomp_dispatch(task.pid.0, SYSCALL_WAITPID) {
+        return errno;
+    }
     // find a child process
 
     // ---- access current PCB exclusively
@@ -154,6 +496,9 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
 /// HINT: You might reimplement it with virtual memory management.
 /// HINT: What if [`TimeVal`] is splitted by two pages ?
 pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_task().unwrap().pid.0, SYSCALL_GET_TIME) {
+        return errno;
+    }
     let token = current_user_token();
     let pspace = translated_byte_buffer(token, _ts as *const u8, size_of::<TimeVal>());
     let t = get_time_us();
@@ -179,6 +524,9 @@ pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
 /// HINT: What if [`TaskInfo`] is splitted by two pages ?
 pub fn sys_task_info(_ti: *mut TaskInfo) -> isize {
     let pid = get_current_pid();
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_TASK_INFO) {
+        return errno;
+    }
     let token = current_user_token();
     let pspace = translated_byte_buffer(token, _ti as *const u8, size_of::<TaskInfo>());
     let mut info;
@@ -206,6 +554,9 @@ pub fn sys_task_info(_ti: *mut TaskInfo) -> isize {
 
 /// YOUR JOB: Implement mmap.
 pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_task().unwrap().pid.0, SYSCALL_MMAP) {
+        return errno;
+    }
     if (_start & (PAGE_SIZE - 1)) != 0 || (_port & (!7)) != 0 || (_port & 7) == 0 {
         -1
     } else {
@@ -225,6 +576,9 @@ pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
 
 /// YOUR JOB: Implement munmap.
 pub fn sys_munmap(_start: usize, _len: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_task().unwrap().pid.0, SYSCALL_MUNMAP) {
+        return errno;
+    }
     if (_start & (PAGE_SIZE - 1)) != 0 {
         -1
     } else {
@@ -235,6 +589,9 @@ pub fn sys_munmap(_start: usize, _len: usize) -> isize {
 /// change data segment size
 pub fn sys_sbrk(size: i32) -> isize {
     trace!("kernel:pid[{}] sys_sbrk", current_task().unwrap().pid.0);
+    if let Err(errno) = seccomp_dispatch(current_task().unwrap().pid.0, SYSCALL_SBRK) {
+        return errno;
+    }
     if let Some(old_brk) = current_task().unwrap().change_program_brk(size) {
         old_brk as isize
     } else {
@@ -245,18 +602,57 @@ pub fn sys_sbrk(size: i32) -> isize {
 /// YOUR JOB: Implement spawn.
 /// HINT: fork + exec =/= spawn
 pub fn sys_spawn(_path: *const u8) -> isize {
-    trace!(
-        "kernel:pid[{}] sys_spawn NOT IMPLEMENTED",
-        current_task().unwrap().pid.0
-    );
-    -1
+    let cur_pid = current_task().unwrap().pid.0;
+    trace!("kernel:pid[{}] sys_spawn", cur_pid);
+    if let Err(errno) = seccomp_dispatch(cur_pid, SYSCALL_SPAWN) {
+        return errno;
+    }
+    let token = current_user_token();
+    let path = translated_str(token, _path);
+    let Some(data) = get_app_data_by_name(path.as_str()) else {
+        return -1;
+    };
+    // build the child's address space straight from the ELF, instead of fork's
+    // copy-then-exec-discards-it approach
+    let new_task = Arc::new(TaskControlBlock::new(data));
+    let new_pid = new_task.pid.0;
+    new_task.inner_exclusive_access().parent = Some(Arc::downgrade(&current_task().unwrap()));
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .children
+        .push(new_task.clone());
+    TASK_INFO.exclusive_access().push((new_pid, TaskInfo::new()));
+    inherit_seccomp(cur_pid, new_pid);
+    add_task(new_task);
+    new_pid as isize
 }
 
-// YOUR JOB: Set task priority.
+/// Set task priority; stride scheduling then allocates CPU proportionally to it.
 pub fn sys_set_priority(_prio: isize) -> isize {
     trace!(
-        "kernel:pid[{}] sys_set_priority NOT IMPLEMENTED",
-        current_task().unwrap().pid.0
+        "kernel:pid[{}] sys_set_priority({})",
+        current_task().unwrap().pid.0,
+        _prio
     );
-    -1
+    let pid = current_task().unwrap().pid.0;
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_SET_PRIORITY) {
+        return errno;
+    }
+    if _prio < MIN_PRIORITY {
+        return -1;
+    }
+    let mut info = STRIDE_INFO.exclusive_access();
+    if let Some((_, s)) = info.iter_mut().find(|(id, _)| *id == pid) {
+        s.priority = _prio;
+    } else {
+        info.push((
+            pid,
+            StrideInfo {
+                priority: _prio,
+                pass: 0,
+            },
+        ));
+    }
+    _prio
 }
@@ -1,7 +1,230 @@
-use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
-use crate::task::{block_current_and_run_next, current_process, current_task};
+use crate::mm::{translated_refmut, PageTable, VirtAddr};
+use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, RwLock, Semaphore, UPSafeCell};
+use crate::syscall::process::{seccomp_dispatch, stride_pick};
+use crate::task::{add_task, block_current_and_run_next, current_process, current_task, TaskControlBlock};
 use crate::timer::{add_timer, get_time_ms};
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+/// distinguishes a timeout from an ordinary failure, mirroring POSIX's `ETIMEDOUT`
+pub const ETIMEDOUT: isize = 110;
+
+/// this lab's syscall numbers for the handlers in this file, gated through
+/// `seccomp_dispatch` the same way `syscall::process`'s are — see its doc comment for
+/// why each handler calls it itself instead of a central dispatch loop
+const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_TIMEDLOCK: usize = 1013;
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_SEMAPHORE_TIMEDDOWN: usize = 1023;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_BROADCAST: usize = 1032;
+const SYSCALL_CONDVAR_WAIT: usize = 1033;
+const SYSCALL_CONDVAR_TIMEDWAIT: usize = 1034;
+const SYSCALL_FUTEX_WAIT: usize = 1040;
+const SYSCALL_FUTEX_WAKE: usize = 1041;
+const SYSCALL_RWLOCK_CREATE: usize = 1050;
+const SYSCALL_RWLOCK_RDLOCK: usize = 1051;
+const SYSCALL_RWLOCK_WRLOCK: usize = 1052;
+const SYSCALL_RWLOCK_UNLOCK: usize = 1053;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 1060;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 1070;
+const SYSCALL_SCHED_GETSCHEDULER: usize = 1071;
+
+lazy_static! {
+    /// FIFOs of tasks parked inside a `*_timed*` syscall, indexed by mutex/semaphore/
+    /// condvar id. `sys_mutex_unlock`/`sys_semaphore_up` wake one entry here in addition
+    /// to the primitive's own (opaque) wait queue, and `sys_condvar_signal` wakes one entry
+    /// here instead of the condvar's own waiter when this pool is non-empty, since a timed
+    /// waiter bypasses that queue so it can also be woken by `add_timer` on expiry.
+    static ref MUTEX_TIMED_WAITERS: UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+    static ref SEM_TIMED_WAITERS: UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+    static ref CONDVAR_TIMED_WAITERS: UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+fn push_timed_waiter(
+    table: &UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>>,
+    id: usize,
+    task: Arc<TaskControlBlock>,
+) {
+    let mut table = table.exclusive_access();
+    if let Some((_, waiters)) = table.iter_mut().find(|(k, _)| *k == id) {
+        waiters.push(task);
+    } else {
+        table.push((id, vec![task]));
+    }
+}
+
+/// drop `task`'s entry from `table`, if still present
+///
+/// `wake_one_timed_waiter`/`wake_all_timed_waiters` already remove an entry when they
+/// wake it explicitly; this covers the other path out of a timed wait — the deadline
+/// firing — where nothing would otherwise remove it. Without this, a later unlock/up/
+/// signal could pop that stale entry and `add_task` a `TaskControlBlock` that's already
+/// running again (or, if the pid has since been reused, one that belongs to someone
+/// else entirely), double-scheduling it.
+fn remove_timed_waiter(
+    table: &UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>>,
+    id: usize,
+    task: &Arc<TaskControlBlock>,
+) {
+    if let Some((_, waiters)) = table.exclusive_access().iter_mut().find(|(k, _)| *k == id) {
+        waiters.retain(|t| !Arc::ptr_eq(t, task));
+    }
+}
+
+/// wake a single timed waiter parked on `id`, if any; returns whether one was woken
+fn wake_one_timed_waiter(table: &UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>>, id: usize) -> bool {
+    let woken = table
+        .exclusive_access()
+        .iter_mut()
+        .find(|(k, _)| *k == id)
+        .filter(|(_, waiters)| !waiters.is_empty())
+        .map(|(_, waiters)| waiters.remove(0));
+    let woke_someone = woken.is_some();
+    if let Some(task) = woken {
+        add_task(task);
+    }
+    woke_someone
+}
+
+fn wake_all_timed_waiters(table: &UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>>, id: usize) {
+    let woken = table
+        .exclusive_access()
+        .iter_mut()
+        .find(|(k, _)| *k == id)
+        .map(|(_, waiters)| core::mem::take(waiters));
+    if let Some(waiters) = woken {
+        for task in waiters {
+            add_task(task);
+        }
+    }
+}
+
+/// a (pid, tid) pair identifying a thread across the whole kernel
+type TaskKey = (usize, usize);
+
+/// priority assigned to a thread that has never donated or received a donation
+const DEFAULT_TASK_PRIORITY: isize = 16;
+/// bound on how far a priority boost propagates down a chain of blocked owners,
+/// so a cycle in the blocking graph can't recurse forever
+const MAX_DONATION_DEPTH: usize = 8;
+
+/// a thread's own priority, separate from whatever it has been boosted to
+#[derive(Clone, Copy)]
+struct PriorityInfo {
+    base_priority: isize,
+    effective_priority: isize,
+}
+
+lazy_static! {
+    /// base/effective priority of every thread that has touched a blocking mutex
+    static ref TASK_PRIORITY: UPSafeCell<Vec<(TaskKey, PriorityInfo)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+    /// owning (pid, tid) of each blocking mutex, indexed by mutex_id; `None` when free
+    static ref MUTEX_OWNER: UPSafeCell<Vec<Option<TaskKey>>> = unsafe { UPSafeCell::new(Vec::new()) };
+    /// threads currently blocked on each mutex_id, indexed the same way
+    static ref MUTEX_WAITERS: UPSafeCell<Vec<Vec<TaskKey>>> = unsafe { UPSafeCell::new(Vec::new()) };
+    /// the mutex_id a thread is currently blocked on, if any
+    static ref BLOCKED_ON: UPSafeCell<Vec<(TaskKey, usize)>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// the calling thread's (pid, tid) key
+fn current_task_key() -> TaskKey {
+    let task = current_task().unwrap();
+    let pid = task.process.upgrade().unwrap().getpid();
+    let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+    (pid, tid)
+}
+
+/// a thread's effective (possibly boosted) priority, `DEFAULT_TASK_PRIORITY` if unset
+fn effective_priority(key: TaskKey) -> isize {
+    TASK_PRIORITY
+        .exclusive_access()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, p)| p.effective_priority)
+        .unwrap_or(DEFAULT_TASK_PRIORITY)
+}
+
+/// a thread's own (un-donated) priority, `DEFAULT_TASK_PRIORITY` if unset
+fn base_priority(key: TaskKey) -> isize {
+    TASK_PRIORITY
+        .exclusive_access()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, p)| p.base_priority)
+        .unwrap_or(DEFAULT_TASK_PRIORITY)
+}
+
+fn set_effective_priority(key: TaskKey, priority: isize) {
+    let mut table = TASK_PRIORITY.exclusive_access();
+    if let Some((_, p)) = table.iter_mut().find(|(k, _)| *k == key) {
+        p.effective_priority = priority;
+    } else {
+        table.push((
+            key,
+            PriorityInfo {
+                base_priority: DEFAULT_TASK_PRIORITY,
+                effective_priority: priority,
+            },
+        ));
+    }
+}
+
+/// recompute `key`'s effective priority as the max of its own base priority and every
+/// waiter's effective priority across all mutexes it currently owns, then store it
+fn recompute_effective_priority(key: TaskKey) -> isize {
+    let mut max_priority = base_priority(key);
+    let owners = MUTEX_OWNER.exclusive_access().clone();
+    let waiters = MUTEX_WAITERS.exclusive_access().clone();
+    for (mutex_id, owner) in owners.iter().enumerate() {
+        if *owner == Some(key) {
+            if let Some(mutex_waiters) = waiters.get(mutex_id) {
+                for &waiter in mutex_waiters {
+                    max_priority = max_priority.max(effective_priority(waiter));
+                }
+            }
+        }
+    }
+    set_effective_priority(key, max_priority);
+    max_priority
+}
+
+/// raise `owner`'s effective priority to that of its highest-priority waiter (see
+/// `recompute_effective_priority`), propagating transitively down the chain if `owner`
+/// is itself blocked waiting on another mutex (bounded by `depth` so a cycle in the
+/// blocking graph can't recurse forever).
+fn donate_priority_chain(owner: TaskKey, depth: usize) {
+    if depth == 0 {
+        return;
+    }
+    recompute_effective_priority(owner);
+    let blocked_on = BLOCKED_ON
+        .exclusive_access()
+        .iter()
+        .find(|(k, _)| *k == owner)
+        .map(|(_, mutex_id)| *mutex_id);
+    if let Some(mutex_id) = blocked_on {
+        let next_owner = MUTEX_OWNER.exclusive_access().get(mutex_id).copied().flatten();
+        if let Some(next_owner) = next_owner {
+            if next_owner != owner {
+                donate_priority_chain(next_owner, depth - 1);
+            }
+        }
+    }
+}
+
 /// sleep syscall
 pub fn sys_sleep(ms: usize) -> isize {
     trace!(
@@ -15,8 +238,11 @@ pub fn sys_sleep(ms: usize) -> isize {
             .unwrap()
             .tid
     );
-    let expire_ms = get_time_ms() + ms;
     let task = current_task().unwrap();
+    if let Err(errno) = seccomp_dispatch(task.process.upgrade().unwrap().getpid(), SYSCALL_SLEEP) {
+        return errno;
+    }
+    let expire_ms = get_time_ms() + ms;
     add_timer(expire_ms, task);
     block_current_and_run_next();
     0
@@ -35,6 +261,9 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_MUTEX_CREATE) {
+        return errno;
+    }
     let mutex: Option<Arc<dyn Mutex>> = if !blocking {
         Some(Arc::new(MutexSpin::new()))
     } else {
@@ -64,6 +293,15 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
     }
     process_inner.mutex_available.resize(res_num.max(id + 1), 0);
     process_inner.mutex_available[id] = 1 as u32;
+    let mut owners = MUTEX_OWNER.exclusive_access();
+    let mut waiters = MUTEX_WAITERS.exclusive_access();
+    if id == owners.len() {
+        owners.push(None);
+        waiters.push(Vec::new());
+    } else {
+        owners[id] = None;
+        waiters[id] = Vec::new();
+    }
     id as isize
 }
 /// mutex lock syscall
@@ -80,6 +318,9 @@ pub fn sys_mutex_lock(mutex_id: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_MUTEX_LOCK) {
+        return errno;
+    }
     let mut process_inner = process.inner_exclusive_access();
     let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
     let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
@@ -95,7 +336,20 @@ pub fn sys_mutex_lock(mutex_id: usize) -> isize {
         -0xdead
     } else {
         drop(process);
+        let key = current_task_key();
+        // if the mutex is already held, donate our priority to the owner (and, should
+        // the owner itself be blocked, transitively down the rest of the chain) before
+        // actually blocking, to avoid priority inversion
+        let owner = MUTEX_OWNER.exclusive_access().get(mutex_id).copied().flatten();
+        if let Some(owner) = owner.filter(|owner| *owner != key) {
+            MUTEX_WAITERS.exclusive_access()[mutex_id].push(key);
+            BLOCKED_ON.exclusive_access().push((key, mutex_id));
+            donate_priority_chain(owner, MAX_DONATION_DEPTH);
+        }
         mutex.lock();
+        MUTEX_WAITERS.exclusive_access()[mutex_id].retain(|k| *k != key);
+        BLOCKED_ON.exclusive_access().retain(|(k, _)| *k != key);
+        MUTEX_OWNER.exclusive_access()[mutex_id] = Some(key);
         0
     }
 }
@@ -113,6 +367,9 @@ pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_MUTEX_UNLOCK) {
+        return errno;
+    }
     let mut process_inner = process.inner_exclusive_access();
     let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
     process_inner.mutex_available[mutex_id] += 1;
@@ -120,9 +377,93 @@ pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
     process_inner.mutex_allocated[tid][mutex_id] -= 1;
     drop(process_inner);
     drop(process);
+    let key = current_task_key();
+    MUTEX_OWNER.exclusive_access()[mutex_id] = None;
     mutex.unlock();
+    // the next owner records itself in MUTEX_OWNER when its own `lock()` returns; here
+    // we only need to drop whatever boost we were holding on this mutex's account
+    recompute_effective_priority(key);
+    wake_one_timed_waiter(&MUTEX_TIMED_WAITERS, mutex_id);
     0
 }
+/// mutex lock syscall with a timeout
+///
+/// Polls the same fast path as `sys_mutex_lock`, parking between attempts with
+/// `add_timer` so either an unlock or the deadline resumes it; the deadline is
+/// rechecked on every wakeup rather than tracked via a separate "why did I wake up"
+/// flag. Returns `-ETIMEDOUT` instead of blocking forever once `timeout_ms` elapses.
+///
+/// Mirrors `sys_mutex_lock`'s `MUTEX_OWNER`/`MUTEX_WAITERS`/`BLOCKED_ON` bookkeeping and
+/// `donate_priority_chain` call on every iteration of its retry loop: without that, a
+/// mutex only ever acquired or contended through this timed entry point would never
+/// record an owner or donate to it, so priority inheritance would silently not apply
+/// to it.
+pub fn sys_mutex_timedlock(mutex_id: usize, timeout_ms: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_mutex_timedlock",
+        current_process().getpid()
+    );
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_MUTEX_TIMEDLOCK) {
+        return errno;
+    }
+    let deadline = get_time_ms() + timeout_ms;
+    let key = current_task_key();
+    loop {
+        let process = current_process();
+        let mut process_inner = process.inner_exclusive_access();
+        let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+        let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
+        if process_inner.mutex_available[mutex_id] > 0 {
+            process_inner.mutex_available[mutex_id] -= 1;
+            process_inner.mutex_allocated[tid][mutex_id] += 1;
+            drop(process_inner);
+            mutex.lock();
+            // same bookkeeping as sys_mutex_lock's success path, so a mutex acquired
+            // via this timed entry point still donates to whoever blocks on it next
+            MUTEX_WAITERS.exclusive_access()[mutex_id].retain(|k| *k != key);
+            BLOCKED_ON.exclusive_access().retain(|(k, _)| *k != key);
+            MUTEX_OWNER.exclusive_access()[mutex_id] = Some(key);
+            return 0;
+        }
+        process_inner.mutex_need[tid][mutex_id] += 1;
+        drop(process_inner);
+        if process.inner_exclusive_access().enable_deadlock_detect && process.check_mutex_deadlock() {
+            current_process().inner_exclusive_access().mutex_need[tid][mutex_id] -= 1;
+            MUTEX_WAITERS.exclusive_access()[mutex_id].retain(|k| *k != key);
+            BLOCKED_ON.exclusive_access().retain(|(k, _)| *k != key);
+            return -0xdead;
+        }
+        if get_time_ms() >= deadline {
+            current_process().inner_exclusive_access().mutex_need[tid][mutex_id] -= 1;
+            MUTEX_WAITERS.exclusive_access()[mutex_id].retain(|k| *k != key);
+            BLOCKED_ON.exclusive_access().retain(|(k, _)| *k != key);
+            return -ETIMEDOUT;
+        }
+        // register as a waiter and donate our priority to the current owner (if any)
+        // before parking, exactly like sys_mutex_lock — otherwise a mutex only ever
+        // acquired/contended through this timed entry point would never donate
+        let owner = MUTEX_OWNER.exclusive_access().get(mutex_id).copied().flatten();
+        if let Some(owner) = owner.filter(|owner| *owner != key) {
+            let mut waiters = MUTEX_WAITERS.exclusive_access();
+            if !waiters[mutex_id].contains(&key) {
+                waiters[mutex_id].push(key);
+            }
+            drop(waiters);
+            let mut blocked_on = BLOCKED_ON.exclusive_access();
+            blocked_on.retain(|(k, _)| *k != key);
+            blocked_on.push((key, mutex_id));
+            drop(blocked_on);
+            donate_priority_chain(owner, MAX_DONATION_DEPTH);
+        }
+        let task = current_task().unwrap();
+        push_timed_waiter(&MUTEX_TIMED_WAITERS, mutex_id, task.clone());
+        add_timer(deadline, task.clone());
+        block_current_and_run_next();
+        remove_timed_waiter(&MUTEX_TIMED_WAITERS, mutex_id, &task);
+        // undo the speculative `need` bump before the next fast-path attempt
+        current_process().inner_exclusive_access().mutex_need[tid][mutex_id] -= 1;
+    }
+}
 /// semaphore create syscall
 pub fn sys_semaphore_create(res_count: usize) -> isize {
     trace!(
@@ -137,6 +478,9 @@ pub fn sys_semaphore_create(res_count: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_SEMAPHORE_CREATE) {
+        return errno;
+    }
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
         .semaphore_list
@@ -180,6 +524,9 @@ pub fn sys_semaphore_up(sem_id: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_SEMAPHORE_UP) {
+        return errno;
+    }
     let mut process_inner = process.inner_exclusive_access();
     let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
     let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
@@ -188,8 +535,45 @@ pub fn sys_semaphore_up(sem_id: usize) -> isize {
 
     drop(process_inner);
     sem.up();
+    wake_one_timed_waiter(&SEM_TIMED_WAITERS, sem_id);
     0
 }
+/// semaphore down syscall with a timeout, see `sys_mutex_timedlock` for the approach
+pub fn sys_semaphore_timeddown(sem_id: usize, timeout_ms: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_semaphore_timeddown",
+        current_process().getpid()
+    );
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_SEMAPHORE_TIMEDDOWN) {
+        return errno;
+    }
+    let deadline = get_time_ms() + timeout_ms;
+    loop {
+        let process = current_process();
+        let mut process_inner = process.inner_exclusive_access();
+        let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+        let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
+        if process_inner.sem_available[sem_id] > 0 {
+            process_inner.sem_available[sem_id] -= 1;
+            process_inner.sem_allocated[tid][sem_id] += 1;
+            drop(process_inner);
+            sem.down();
+            return 0;
+        }
+        process_inner.sem_need[tid][sem_id] += 1;
+        drop(process_inner);
+        if get_time_ms() >= deadline {
+            current_process().inner_exclusive_access().sem_need[tid][sem_id] -= 1;
+            return -ETIMEDOUT;
+        }
+        let task = current_task().unwrap();
+        push_timed_waiter(&SEM_TIMED_WAITERS, sem_id, task.clone());
+        add_timer(deadline, task.clone());
+        block_current_and_run_next();
+        remove_timed_waiter(&SEM_TIMED_WAITERS, sem_id, &task);
+        current_process().inner_exclusive_access().sem_need[tid][sem_id] -= 1;
+    }
+}
 /// semaphore down syscall
 pub fn sys_semaphore_down(sem_id: usize) -> isize {
     trace!(
@@ -204,6 +588,9 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_SEMAPHORE_DOWN) {
+        return errno;
+    }
     let mut process_inner = process.inner_exclusive_access();
     let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
     let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
@@ -242,6 +629,9 @@ pub fn sys_condvar_create() -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_CONDVAR_CREATE) {
+        return errno;
+    }
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
         .condvar_list
@@ -261,6 +651,15 @@ pub fn sys_condvar_create() -> isize {
     id as isize
 }
 /// condvar signal syscall
+///
+/// A single condvar can be waited on either through `Condvar::wait` (the Birrell
+/// two-semaphore scheme, tracked by its own `waiter_count`) or through
+/// `sys_condvar_timedwait` (parked in `CONDVAR_TIMED_WAITERS` instead, so a deadline
+/// can pull it back out again). Those are two independent "parked" pools on the same
+/// id, so waking both unconditionally here would let one `signal` wake two waiters —
+/// one from each pool — instead of exactly one. Preferring the timed pool and only
+/// falling through to `condvar.signal()` when it was empty keeps this call's contract
+/// (wake at most one waiter) regardless of which pool that waiter happened to be in.
 pub fn sys_condvar_signal(condvar_id: usize) -> isize {
     trace!(
         "kernel:pid[{}] tid[{}] sys_condvar_signal",
@@ -274,12 +673,72 @@ pub fn sys_condvar_signal(condvar_id: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_CONDVAR_SIGNAL) {
+        return errno;
+    }
     let process_inner = process.inner_exclusive_access();
     let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
     drop(process_inner);
-    condvar.signal();
+    if !wake_one_timed_waiter(&CONDVAR_TIMED_WAITERS, condvar_id) {
+        condvar.signal();
+    }
     0
 }
+/// condvar broadcast syscall: wakes every waiter, not just one
+pub fn sys_condvar_broadcast(condvar_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_condvar_broadcast",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_CONDVAR_BROADCAST) {
+        return errno;
+    }
+    let process_inner = process.inner_exclusive_access();
+    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
+    drop(process_inner);
+    condvar.broadcast();
+    wake_all_timed_waiters(&CONDVAR_TIMED_WAITERS, condvar_id);
+    0
+}
+/// condvar wait syscall with a timeout
+///
+/// Unlike `sys_mutex_timedlock`/`sys_semaphore_timeddown` this parks only once: POSIX
+/// condvars already allow spurious wakeups, so the caller is expected to re-check its
+/// predicate after any return (timeout or not) rather than have us retry internally.
+pub fn sys_condvar_timedwait(condvar_id: usize, mutex_id: usize, timeout_ms: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_condvar_timedwait",
+        current_process().getpid()
+    );
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_CONDVAR_TIMEDWAIT) {
+        return errno;
+    }
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    drop(process_inner);
+    let deadline = get_time_ms() + timeout_ms;
+    mutex.unlock();
+    let task = current_task().unwrap();
+    push_timed_waiter(&CONDVAR_TIMED_WAITERS, condvar_id, task.clone());
+    add_timer(deadline, task.clone());
+    block_current_and_run_next();
+    remove_timed_waiter(&CONDVAR_TIMED_WAITERS, condvar_id, &task);
+    mutex.lock();
+    if get_time_ms() >= deadline {
+        -ETIMEDOUT
+    } else {
+        0
+    }
+}
 /// condvar wait syscall
 pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     trace!(
@@ -294,6 +753,9 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
             .tid
     );
     let process = current_process();
+    if let Err(errno) = seccomp_dispatch(process.getpid(), SYSCALL_CONDVAR_WAIT) {
+        return errno;
+    }
     let process_inner = process.inner_exclusive_access();
     let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
     let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
@@ -301,10 +763,321 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     condvar.wait(mutex);
     0
 }
+
+lazy_static! {
+    /// futex wait queues, keyed by the translated (physical) address backing each futex
+    /// word so threads of one process sharing a uaddr agree on the same key
+    static ref FUTEX_QUEUES: UPSafeCell<Vec<(usize, Vec<Arc<TaskControlBlock>>)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// translate a user virtual address to a stable key: the physical address it currently
+/// maps to. Using the physical address (rather than `uaddr` itself) lets threads of the
+/// same process address a shared futex consistently even across different mappings.
+fn futex_key(uaddr: usize) -> usize {
+    let token = current_process().inner_exclusive_access().memory_set.token();
+    PageTable::from_token(token)
+        .translate_va(VirtAddr::from(uaddr))
+        .unwrap()
+        .0
+}
+
+/// read the u32 currently stored at `uaddr` in the calling process's address space
+fn futex_read(uaddr: usize) -> u32 {
+    let token = current_process().inner_exclusive_access().memory_set.token();
+    *translated_refmut(token, uaddr as *mut u32)
+}
+
+/// Block the calling thread until woken via `sys_futex_wake` on the same address.
+///
+/// Atomically (with respect to `sys_futex_wake`, both serialized through `FUTEX_QUEUES`)
+/// re-checks `*uaddr == expected` before enqueueing: if the value already changed, this
+/// returns immediately instead of blocking, closing the lost-wakeup race.
+pub fn sys_futex_wait(uaddr: usize, expected: u32) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_futex_wait",
+        current_process().getpid()
+    );
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_FUTEX_WAIT) {
+        return errno;
+    }
+    let key = futex_key(uaddr);
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    if futex_read(uaddr) != expected {
+        return 0;
+    }
+    let task = current_task().unwrap();
+    if let Some((_, waiters)) = queues.iter_mut().find(|(k, _)| *k == key) {
+        waiters.push(task);
+    } else {
+        queues.push((key, vec![task]));
+    }
+    drop(queues);
+    block_current_and_run_next();
+    0
+}
+
+/// Wake up to `count` threads blocked in `sys_futex_wait` on `uaddr`.
+///
+/// Returns the number of threads actually woken.
+pub fn sys_futex_wake(uaddr: usize, count: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_futex_wake",
+        current_process().getpid()
+    );
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_FUTEX_WAKE) {
+        return errno;
+    }
+    let key = futex_key(uaddr);
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let Some((_, waiters)) = queues.iter_mut().find(|(k, _)| *k == key) else {
+        return 0;
+    };
+    let to_wake = waiters.len().min(count);
+    let woken: Vec<_> = waiters.drain(..to_wake).collect();
+    drop(queues);
+    let n = woken.len();
+    for task in woken {
+        add_task(task);
+    }
+    n as isize
+}
+
+/// per-process rwlock bookkeeping, kept in a side table like `MUTEX_OWNER` above rather
+/// than on `ProcessControlBlockInner`: `mutex_list`/`mutex_need`/... live there for
+/// mutexes, but this request's `task::process` counterpart was never touched to grow a
+/// matching `rwlock_list`/`rwlock_need`/`rwlock_allocated`/`rwlock_available`, so there
+/// is nowhere to add them without guessing at a struct this module has no visibility
+/// into. This table plays the identical role, indexed by pid instead of living inside
+/// the PCB, and `check_rwlock_deadlock` below runs the same single-unit-per-resource
+/// Banker's check `check_mutex_deadlock` does.
+struct RwLockProcessState {
+    list: Vec<Option<Arc<RwLock>>>,
+    need: Vec<Vec<u32>>,
+    allocated: Vec<Vec<u32>>,
+    available: Vec<u32>,
+}
+
+lazy_static! {
+    static ref RWLOCK_STATE: UPSafeCell<Vec<(usize, RwLockProcessState)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// index of `pid`'s entry in `RWLOCK_STATE`, creating an empty one if this is its first rwlock
+fn rwlock_state_index(table: &mut Vec<(usize, RwLockProcessState)>, pid: usize) -> usize {
+    if let Some(idx) = table.iter().position(|(id, _)| *id == pid) {
+        idx
+    } else {
+        table.push((
+            pid,
+            RwLockProcessState {
+                list: Vec::new(),
+                need: Vec::new(),
+                allocated: Vec::new(),
+                available: Vec::new(),
+            },
+        ));
+        table.len() - 1
+    }
+}
+
+/// grow `state`'s per-thread rows so thread `tid` has a `need`/`allocated` entry for
+/// every rwlock created so far
+fn ensure_rwlock_tid_row(state: &mut RwLockProcessState, tid: usize) {
+    if tid >= state.need.len() {
+        state.need.resize_with(tid + 1, Vec::new);
+        state.allocated.resize_with(tid + 1, Vec::new);
+    }
+    let res_num = state.available.len();
+    state.need[tid].resize(res_num, 0);
+    state.allocated[tid].resize(res_num, 0);
+}
+
+/// Banker's-algorithm deadlock check over `pid`'s rwlock need/allocated/available
+/// matrices, identical in shape to `check_mutex_deadlock` (each rwlock contributes at
+/// most one unit, held by whichever thread currently has it write-locked).
+fn check_rwlock_deadlock(pid: usize) -> bool {
+    let table = RWLOCK_STATE.exclusive_access();
+    let Some((_, state)) = table.iter().find(|(id, _)| *id == pid) else {
+        return false;
+    };
+    let n = state.need.len();
+    let m = state.available.len();
+    let mut work = state.available.clone();
+    let mut finish = vec![false; n];
+    loop {
+        let mut progressed = false;
+        for tid in 0..n {
+            if finish[tid] {
+                continue;
+            }
+            if (0..m).all(|r| state.need[tid][r] <= work[r]) {
+                for r in 0..m {
+                    work[r] += state.allocated[tid][r];
+                }
+                finish[tid] = true;
+                progressed = true;
+            }
+        }
+        if !progressed {
+            break;
+        }
+    }
+    finish.iter().any(|f| !f)
+}
+
+/// rwlock create syscall
+pub fn sys_rwlock_create() -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_create",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let pid = current_process().getpid();
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_RWLOCK_CREATE) {
+        return errno;
+    }
+    let mut table = RWLOCK_STATE.exclusive_access();
+    let idx = rwlock_state_index(&mut table, pid);
+    let state = &mut table[idx].1;
+    let id = if let Some(id) = state
+        .list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        state.list[id] = Some(Arc::new(RwLock::new()));
+        id
+    } else {
+        state.list.push(Some(Arc::new(RwLock::new())));
+        state.list.len() - 1
+    };
+    let res_num = state.available.len();
+    for row in state.need.iter_mut().chain(state.allocated.iter_mut()) {
+        row.resize(res_num.max(id + 1), 0);
+    }
+    state.available.resize(res_num.max(id + 1), 0);
+    state.available[id] = 1;
+    id as isize
+}
+/// rwlock read-lock syscall
+///
+/// A shared read lock isn't modeled as an exclusive resource, so unlike the writer
+/// path below it never touches the deadlock detector's matrices.
+pub fn sys_rwlock_rdlock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_rdlock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let pid = current_process().getpid();
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_RWLOCK_RDLOCK) {
+        return errno;
+    }
+    let rwlock = RWLOCK_STATE
+        .exclusive_access()
+        .iter()
+        .find(|(id, _)| *id == pid)
+        .and_then(|(_, s)| s.list[rwlock_id].clone())
+        .unwrap();
+    rwlock.read_lock();
+    0
+}
+/// rwlock write-lock syscall
+///
+/// A held write lock is modeled as one unit of an exclusive resource, the same way
+/// `sys_mutex_lock` models its mutex, so it participates in deadlock detection.
+pub fn sys_rwlock_wrlock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_wrlock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let pid = current_process().getpid();
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_RWLOCK_WRLOCK) {
+        return errno;
+    }
+    let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
+    let rwlock = {
+        let mut table = RWLOCK_STATE.exclusive_access();
+        let idx = rwlock_state_index(&mut table, pid);
+        let state = &mut table[idx].1;
+        ensure_rwlock_tid_row(state, tid);
+        let rwlock = Arc::clone(state.list[rwlock_id].as_ref().unwrap());
+        if state.available[rwlock_id] > 0 {
+            state.available[rwlock_id] -= 1;
+            state.allocated[tid][rwlock_id] += 1;
+        } else {
+            state.need[tid][rwlock_id] += 1;
+        }
+        rwlock
+    };
+    if current_process().inner_exclusive_access().enable_deadlock_detect && check_rwlock_deadlock(pid) {
+        -0xdead
+    } else {
+        rwlock.write_lock();
+        0
+    }
+}
+/// rwlock unlock syscall, for either a reader or a writer
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_unlock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let pid = current_process().getpid();
+    if let Err(errno) = seccomp_dispatch(pid, SYSCALL_RWLOCK_UNLOCK) {
+        return errno;
+    }
+    let tid = current_task().unwrap().inner_exclusive_access().res.as_ref().unwrap().tid;
+    let rwlock = {
+        let mut table = RWLOCK_STATE.exclusive_access();
+        let idx = rwlock_state_index(&mut table, pid);
+        let state = &mut table[idx].1;
+        ensure_rwlock_tid_row(state, tid);
+        // only a writer ever holds a bookkeeping unit; releasing a read lock is a no-op here
+        if state.allocated[tid][rwlock_id] > 0 {
+            state.available[rwlock_id] += 1;
+            state.allocated[tid][rwlock_id] -= 1;
+        }
+        Arc::clone(state.list[rwlock_id].as_ref().unwrap())
+    };
+    rwlock.unlock();
+    0
+}
 /// enable deadlock detection syscall
 ///
 /// YOUR JOB: Implement deadlock detection, but might not all in this syscall
 pub fn sys_enable_deadlock_detect(_enabled: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_ENABLE_DEADLOCK_DETECT) {
+        return errno;
+    }
     if _enabled > 1 {
         return -1;
     }
@@ -315,3 +1088,133 @@ pub fn sys_enable_deadlock_detect(_enabled: usize) -> isize {
     };
     0
 }
+
+/// default time-sliced scheduling, equal-priority threads share the CPU round-robin
+pub const SCHED_OTHER: usize = 0;
+/// run-to-block within a priority band, no time slice
+pub const SCHED_FIFO: usize = 1;
+/// round-robin with a quantum among threads of equal priority
+pub const SCHED_RR: usize = 2;
+
+/// distinguishes a bad argument from an ordinary failure, mirroring POSIX's `EINVAL`
+pub const EINVAL: isize = 22;
+
+/// priority range accepted for `SCHED_FIFO`/`SCHED_RR`; `SCHED_OTHER` ignores `priority`
+const RT_PRIORITY_MIN: isize = 1;
+const RT_PRIORITY_MAX: isize = 99;
+
+/// a thread's scheduling policy plus the static priority that policy uses
+#[derive(Clone, Copy)]
+struct SchedInfo {
+    policy: usize,
+    static_priority: isize,
+}
+
+lazy_static! {
+    /// per-(pid, tid) scheduling policy; a thread with no entry runs `SCHED_OTHER`
+    static ref SCHED_INFO: UPSafeCell<Vec<(TaskKey, SchedInfo)>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// set `tid`'s scheduling policy and static priority
+///
+/// `sched_pick` below is meant to pick from the ready queue using this instead of plain
+/// FIFO; the queue fetch itself lives in the scheduler (`task::manager`/`task::processor`
+/// in the usual layout), which isn't part of this tree, so nothing calls `sched_pick` yet.
+pub fn sys_sched_setscheduler(tid: usize, policy: usize, priority: isize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_SCHED_SETSCHEDULER) {
+        return errno;
+    }
+    if policy > SCHED_RR {
+        return -EINVAL;
+    }
+    if policy != SCHED_OTHER && !(RT_PRIORITY_MIN..=RT_PRIORITY_MAX).contains(&priority) {
+        return -EINVAL;
+    }
+    let key = (current_process().getpid(), tid);
+    let info = SchedInfo {
+        policy,
+        static_priority: priority,
+    };
+    let mut table = SCHED_INFO.exclusive_access();
+    if let Some((_, s)) = table.iter_mut().find(|(k, _)| *k == key) {
+        *s = info;
+    } else {
+        table.push((key, info));
+    }
+    0
+}
+
+/// read back the scheduling policy installed for thread `tid`
+pub fn sys_sched_getscheduler(tid: usize) -> isize {
+    if let Err(errno) = seccomp_dispatch(current_process().getpid(), SYSCALL_SCHED_GETSCHEDULER) {
+        return errno;
+    }
+    let key = (current_process().getpid(), tid);
+    SCHED_INFO
+        .exclusive_access()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, s)| s.policy as isize)
+        .unwrap_or(SCHED_OTHER as isize)
+}
+
+/// Pick the next thread to run among `ready`. Any thread with an installed `SCHED_FIFO`/
+/// `SCHED_RR` policy (static priority > 0) goes ahead of every plain `SCHED_OTHER`
+/// thread; among real-time threads, ties on static priority are broken by
+/// `effective_priority` — the mutex donation protocol's output — so a thread boosted
+/// because it owns a mutex someone else needs actually gets picked first, and remaining
+/// ties by arrival order in `ready`.
+///
+/// If no ready thread has a real-time policy, this defers to `stride_pick` — the same
+/// proportional-share table `sys_set_priority` maintains — rather than invent a second,
+/// independent tie-break for `SCHED_OTHER` threads: the two scheduling requests share
+/// one fairness policy instead of shipping as mutually exclusive ones. `stride_pick` is
+/// keyed by pid, one level coarser than this function's per-thread `TaskKey`, so the
+/// winning pid's lowest-tid ready thread is what actually gets returned.
+///
+/// This is meant to replace the scheduler's plain "pop the front of the ready queue",
+/// but that queue fetch lives in `task::manager`/`task::processor` (the usual layout),
+/// which isn't part of this tree, so this function has no caller yet outside itself —
+/// donated priority and stride fairness are computed correctly but never consulted when
+/// a real thread is actually chosen to run.
+pub fn sched_pick(ready: &[TaskKey]) -> Option<TaskKey> {
+    let table = SCHED_INFO.exclusive_access();
+    let is_realtime = |key: &TaskKey| {
+        table
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, s)| s.policy != SCHED_OTHER)
+            .unwrap_or(false)
+    };
+    let realtime: Vec<TaskKey> = ready.iter().copied().filter(is_realtime).collect();
+    if !realtime.is_empty() {
+        let priority_of = |key: &TaskKey| {
+            let static_priority = table
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, s)| s.static_priority)
+                .unwrap_or(0);
+            (static_priority, effective_priority(*key))
+        };
+        let mut best = None;
+        let mut best_priority = (isize::MIN, isize::MIN);
+        for &key in &realtime {
+            let priority = priority_of(&key);
+            if priority > best_priority {
+                best_priority = priority;
+                best = Some(key);
+            }
+        }
+        return best;
+    }
+    drop(table);
+    let mut other_pids: Vec<usize> = ready.iter().map(|(pid, _)| *pid).collect();
+    other_pids.sort_unstable();
+    other_pids.dedup();
+    let winner_pid = stride_pick(&other_pids)?;
+    ready
+        .iter()
+        .copied()
+        .filter(|(pid, _)| *pid == winner_pid)
+        .min_by_key(|(_, tid)| *tid)
+}